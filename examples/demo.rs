@@ -7,7 +7,7 @@ struct Demo {
     b: u32,
     #[dynamic((a, b), calc_c)]
     c: u32,
-    #[dynamic((c), calc_d)]
+    #[dynamic((c), calc_d, when = should_calc_d)]
     d: u32,
 }
 
@@ -28,6 +28,10 @@ impl Demo {
         self.c = self.a + self.b
     }
 
+    fn should_calc_d(&self) -> bool {
+        self.c != 0
+    }
+
     fn calc_d(&mut self) {
         println!("calculating d...");
         self.d = self.c + self.c
@@ -49,11 +53,23 @@ fn main() {
     println!("c: {c}");
     println!("d: {d}");
 
-    println!("updating a...");
-    demo.set_a_value(4);
-    println!("updating b...");
-    demo.set_b_value(9);
+    println!("updating a and b in a batch...");
+    demo.batch(|batch| {
+        batch.set_a_value(4);
+        batch.set_b_value(9);
+    });
+
+    //note: calc_c now only runs once, even though both of its dependencies changed
+    let Demo { c, d, .. } = &demo;
+    println!("c: {c}");
+    println!("d: {d}");
+
+    println!("updating a and b to 0...");
+    demo.set_a_value(0);
+    demo.set_b_value(0);
 
+    //note: calc_d still runs when a is set (c is 9 then), but is skipped when b is set since should_calc_d
+    //returns false once c is 0, so d keeps the value calculated after the update to a
     let Demo { c, d, .. } = &demo;
     println!("c: {c}");
     println!("d: {d}");
@@ -40,6 +40,15 @@
 //!
 //! The local method must have the call signature matching `fn name(&mut self)`.
 //!
+//! An optional `when = should_recalculate` guard can be added to skip recomputation unless the guard returns true:
+//! ```ignore
+//! #[dynamic((a, b), calculate_c, when = should_calculate_c)]
+//! c: u32,
+//! ```
+//!
+//! The guard method must have the call signature matching `fn name(&self) -> bool`. When it returns `false`, neither
+//! the calculation method nor the downstream `updated_*` cascade run, so recomputation is suppressed entirely.
+//!
 //! 3. Update the properties using the generated mutate functions
 //! ```ignore
 //! let demo = Demo { a: 1, b: 2, c: 3 };
@@ -90,7 +99,51 @@
 //! }
 //! ```
 //!
-//! Note: be careful not to create cyclic dependencies!
+//! Note: a cyclic dependency between dynamic fields (e.g. `c` depending on `d` and `d` depending on `c`) is rejected
+//! at compile time, with the cycle path reported in the error.
+//!
+//! # Batch updates
+//!
+//! Setting several base properties one at a time recomputes every transitive dependent each time, so a dynamic property
+//! that depends on two changed inputs is recomputed once per input. To recompute each dynamic property at most once,
+//! wrap the updates in a `batch`:
+//!
+//! ```ignore
+//! demo.batch(|batch| {
+//!     batch.set_a_value(4);
+//!     batch.set_b_value(9);
+//! });
+//! ```
+//!
+//! Inside the closure, the setters only flag the property as changed instead of recomputing dependents straight away.
+//! Once the closure returns, the dynamic properties are recomputed in dependency order (each at most once), based on
+//! which of their dependencies changed during the batch.
+//!
+//! # Inspecting the dependency graph
+//!
+//! Enabling the `metadata` cargo feature generates associated functions that expose the dependency graph the macro
+//! already computes, for tooling such as debug UIs, graph visualizers or tests:
+//!
+//! ```ignore
+//! assert_eq!(Demo::dynamic_dependencies(), &[("c", &["a", "b"] as &[&str])]);
+//! assert_eq!(Demo::dynamic_dependents("a"), &["c"]);
+//! ```
+//!
+//! The feature is off by default, so users who don't need it pay nothing for it.
+//!
+//! # Setting fields by name
+//!
+//! Enabling the `reflection` cargo feature generates a reflective setter, useful for config loaders, scripting
+//! bridges or network packet decoding where the field to update isn't known statically:
+//!
+//! ```ignore
+//! demo.set_by_name("a", DemoField::A(4))?;
+//! ```
+//!
+//! A per-struct enum (`DemoField` above) is generated with one variant per non-dynamic field, carrying that
+//! field's type, so the value passed in is still type-checked. Setting an unknown field, or a dynamic field (which
+//! can't be assigned directly), returns a `DemoSetError` instead of panicking. Like `metadata`, this feature is off
+//! by default.
 //!
 //! # Configuration
 //!
@@ -131,6 +184,7 @@ use syn::{
     parse::{Parse, ParseStream},
     parse_macro_input,
     punctuated::Punctuated,
+    spanned::Spanned,
     token, Data, DeriveInput, Fields, Ident, LitStr, Token,
 };
 
@@ -149,16 +203,45 @@ struct DynamicField {
     dependencies: Punctuated<Ident, Token![,]>,
     _comma: Token![,],
     method_name: Ident,
+    //optional `, when = should_update_c` guard; when present, recomputation only runs if it returns true
+    guard: Option<Ident>,
+    //span of the attribute this was parsed from, used to point compile errors at it; set by the
+    //caller after parsing since it isn't part of the attribute's own grammar
+    attr_span: proc_macro2::Span,
 }
 
 impl Parse for DynamicField {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let content;
+        let _paren_token = parenthesized!(content in input);
+        let dependencies = content.parse_terminated(Ident::parse)?;
+        let _comma = input.parse()?;
+        let method_name = input.parse()?;
+
+        let guard = if input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+
+            let keyword: Ident = input.parse()?;
+            if keyword != "when" {
+                return Err(syn::Error::new(
+                    keyword.span(),
+                    "expected `when` (e.g. `when = should_update_c`)",
+                ));
+            }
+
+            input.parse::<Token![=]>()?;
+            Some(input.parse::<Ident>()?)
+        } else {
+            None
+        };
+
         Ok(DynamicField {
-            _paren_token: parenthesized!(content in input),
-            dependencies: content.parse_terminated(Ident::parse)?,
-            _comma: input.parse()?,
-            method_name: input.parse()?,
+            _paren_token,
+            dependencies,
+            _comma,
+            method_name,
+            guard,
+            attr_span: proc_macro2::Span::call_site(),
         })
     }
 }
@@ -178,16 +261,36 @@ fn create_ident(ident: &Ident, prefix: &str, suffix: &str) -> Ident {
     format_ident!("{}{}{}", prefix, ident, suffix)
 }
 
+//used to turn a snake_case field name into a PascalCase enum variant name, for the `reflection` feature
+fn to_pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
 #[proc_macro_derive(Dynamic, attributes(dynamic))]
 pub fn derive_dynamic(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_dynamic(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+fn expand_dynamic(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let DeriveInput {
-        ident, data, attrs, ..
-    } = parse_macro_input!(input);
+        ident, data, attrs, vis, ..
+    } = input;
 
     //parse and merge the dynamic attribute for the struct
-    let config = Dynamic::try_from_attributes(&attrs)
-        .unwrap()
-        .unwrap_or_default();
+    let config = Dynamic::try_from_attributes(&attrs)?.unwrap_or_default();
 
     let updated_method_prefix = config
         .updated_prefix
@@ -235,9 +338,25 @@ pub fn derive_dynamic(input: proc_macro::TokenStream) -> proc_macro::TokenStream
     let fields = match data {
         Data::Struct(data_struct) => match data_struct.fields {
             Fields::Named(fields) => fields.named,
-            _ => panic!("Only structs with named fields currently supported!"),
+            other => {
+                return Err(syn::Error::new_spanned(
+                    other,
+                    "Only structs with named fields currently supported!",
+                ))
+            }
         },
-        _ => panic!("Only structs currently supported!"),
+        Data::Enum(data_enum) => {
+            return Err(syn::Error::new_spanned(
+                data_enum.enum_token,
+                "Only structs currently supported!",
+            ))
+        }
+        Data::Union(data_union) => {
+            return Err(syn::Error::new_spanned(
+                data_union.union_token,
+                "Only structs currently supported!",
+            ))
+        }
     };
 
     //parse the field 'dynamic' attributes
@@ -254,15 +373,67 @@ pub fn derive_dynamic(input: proc_macro::TokenStream) -> proc_macro::TokenStream
                         .filter(|item| *item == DYNAMIC_ATTR_NAME)
                         .is_some()
                 })
-                .map(|attr| {
-                    attr.parse_args::<DynamicField>()
-                        .expect("Dynamic attribute format is invalid")
-                });
+                .map(|attr| -> syn::Result<DynamicField> {
+                    let mut dynamic = attr.parse_args::<DynamicField>()?;
+                    dynamic.attr_span = attr.span();
+                    Ok(dynamic)
+                })
+                .transpose()?;
 
-            (field, dynamic)
+            Ok((field, dynamic))
         })
+        .collect::<syn::Result<Vec<_>>>()?
+        .into_iter()
         .partition(|(_, dynamic)| dynamic.is_some());
 
+    //validate that each dynamic field has at least one dependency, that every dependency names a field
+    //of the struct, and that the calculation method isn't named after the field it calculates; whether
+    //the calculation method itself exists is not checked here and is left to the compiler
+    let field_names: HashSet<&Ident> = fields.iter().map(|field| field.ident.as_ref().unwrap()).collect();
+
+    let mut validation_errors: Vec<syn::Error> = Vec::new();
+
+    dynamic_fields.iter().for_each(|(field, dynamic)| {
+        let field_name = field.ident.as_ref().unwrap();
+        let dynamic = dynamic.as_ref().unwrap();
+
+        if dynamic.dependencies.is_empty() {
+            validation_errors.push(syn::Error::new(
+                dynamic.attr_span,
+                format!("`{}` has no dependencies; a dynamic field needs at least one", field_name),
+            ));
+        }
+
+        dynamic.dependencies.iter().for_each(|dependency| {
+            if !field_names.contains(dependency) {
+                validation_errors.push(syn::Error::new_spanned(
+                    dependency,
+                    format!(
+                        "`{}` is not a field of `{}`, so it cannot be used as a dependency of `{}`",
+                        dependency, ident, field_name
+                    ),
+                ));
+            }
+        });
+
+        if &dynamic.method_name == field_name {
+            validation_errors.push(syn::Error::new_spanned(
+                &dynamic.method_name,
+                format!(
+                    "the method name `{}` is the same as the field it calculates; did you mean to name the calculation method differently?",
+                    dynamic.method_name
+                ),
+            ));
+        }
+    });
+
+    if let Some(combined) = validation_errors.into_iter().reduce(|mut all, err| {
+        all.combine(err);
+        all
+    }) {
+        return Err(combined);
+    }
+
     //create a list of vars to update based on the dependencies
     let mut inv_map: HashMap<&Ident, HashSet<&Ident>> = HashMap::new();
 
@@ -284,6 +455,128 @@ pub fn derive_dynamic(input: proc_macro::TokenStream) -> proc_macro::TokenStream
             });
     });
 
+    //runtime-queryable view of the dependency graph above, behind the `metadata` feature; snapshot it
+    //now since `updated_methods` below drains `inv_map`
+    let dynamic_dependencies_entries = dynamic_fields.iter().map(|(field, dynamic)| {
+        let field_name = field.ident.as_ref().unwrap().to_string();
+        let dependency_names = dynamic
+            .as_ref()
+            .unwrap()
+            .dependencies
+            .iter()
+            .map(|dependency| dependency.to_string());
+
+        quote! { (#field_name, &[#(#dependency_names),*] as &[&str]) }
+    });
+
+    let dynamic_dependents_entries = inv_map.iter().map(|(dependency, impacted)| {
+        let dependency_name = dependency.to_string();
+        let impacted_names = impacted.iter().map(|field| field.to_string());
+
+        quote! { #dependency_name => &[#(#impacted_names),*] as &[&str], }
+    });
+
+    //gated on our own `metadata` feature (not the destination crate's) since a `#[cfg(feature = ...)]`
+    //emitted into the generated tokens would be evaluated against the destination crate's features instead
+    let metadata_methods = if cfg!(feature = "metadata") {
+        quote! {
+            impl #ident {
+                /// Each dynamic field name paired with the list of fields it depends on.
+                pub fn dynamic_dependencies() -> &'static [(&'static str, &'static [&'static str])] {
+                    &[#(#dynamic_dependencies_entries),*]
+                }
+
+                /// The dynamic fields that directly depend on `field`; fields that only depend on it
+                /// through another dynamic field are not included.
+                pub fn dynamic_dependents(field: &str) -> &'static [&'static str] {
+                    match field {
+                        #(#dynamic_dependents_entries)*
+                        _ => &[],
+                    }
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    //name-keyed setter dispatch, also gated on our own feature for the same reason as `metadata_methods`
+    let reflection_methods = if cfg!(feature = "reflection") {
+        let field_enum_ident = format_ident!("{}Field", ident);
+        let set_error_ident = format_ident!("{}SetError", ident);
+        let ident_name = ident.to_string();
+
+        let field_enum_variants = non_dynamic_fields.iter().map(|(field, _)| {
+            let variant_name = format_ident!("{}", to_pascal_case(&field.ident.as_ref().unwrap().to_string()));
+            let typ = &field.ty;
+
+            quote! { #variant_name(#typ) }
+        });
+
+        let set_by_name_arms = non_dynamic_fields.iter().map(|(field, _)| {
+            let field_name = field.ident.as_ref().unwrap();
+            let field_name_str = field_name.to_string();
+            let variant_name = format_ident!("{}", to_pascal_case(&field_name_str));
+            let setter_func = create_setter_ident(field_name);
+
+            quote! {
+                (#field_name_str, #field_enum_ident::#variant_name(value)) => {
+                    self.#setter_func(value);
+                    Ok(())
+                }
+            }
+        });
+
+        let dynamic_field_arms = dynamic_fields.iter().map(|(field, _)| {
+            let field_name_str = field.ident.as_ref().unwrap().to_string();
+
+            quote! {
+                (#field_name_str, _) => Err(#set_error_ident::DynamicField(field.to_string())),
+            }
+        });
+
+        quote! {
+            #[derive(Debug, Clone, PartialEq)]
+            pub enum #field_enum_ident {
+                #(#field_enum_variants),*
+            }
+
+            #[derive(Debug)]
+            pub enum #set_error_ident {
+                UnknownField(String),
+                DynamicField(String),
+            }
+
+            impl std::fmt::Display for #set_error_ident {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    match self {
+                        #set_error_ident::UnknownField(name) => {
+                            write!(f, "`{}` is not a field of `{}`", name, #ident_name)
+                        }
+                        #set_error_ident::DynamicField(name) => {
+                            write!(f, "`{}` is a dynamic field of `{}` and cannot be set directly", name, #ident_name)
+                        }
+                    }
+                }
+            }
+
+            impl std::error::Error for #set_error_ident {}
+
+            impl #ident {
+                /// Set a non-dynamic field by name, triggering the same `updated_*` cascade as the static setter.
+                pub fn set_by_name(&mut self, field: &str, value: #field_enum_ident) -> Result<(), #set_error_ident> {
+                    match (field, value) {
+                        #(#set_by_name_arms)*
+                        #(#dynamic_field_arms)*
+                        _ => Err(#set_error_ident::UnknownField(field.to_string())),
+                    }
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     //updated methods based on the dependencies
     let updated_methods = fields.iter().map(|field| {
         let field_name = field.ident.as_ref().unwrap();
@@ -320,18 +613,181 @@ pub fn derive_dynamic(input: proc_macro::TokenStream) -> proc_macro::TokenStream
         }
     });
 
-    //update methods for dynamics (calls our desired function)
+    //update methods for dynamics (calls our desired function, guarded by `when` if given)
     let update_methods = dynamic_fields.iter().map(|(field, dynamic)| {
         let field_name = field.ident.as_ref().unwrap();
         let func_name = create_update_ident(field_name);
         let updated_func_name = create_updated_ident(field_name);
-        let callable_name = &dynamic.as_ref().unwrap().method_name;
+        let dynamic = dynamic.as_ref().unwrap();
+        let callable_name = &dynamic.method_name;
+        let recompute = quote! {
+            self.#callable_name();
+            self.#updated_func_name();
+        };
+
+        match &dynamic.guard {
+            Some(guard) => quote! {
+                #[inline]
+                pub fn #func_name(&mut self) {
+                    if self.#guard() {
+                        #recompute
+                    }
+                }
+            },
+            None => quote! {
+                #[inline]
+                pub fn #func_name(&mut self) {
+                    #recompute
+                }
+            },
+        }
+    });
+
+    //work out a compile-time order to recompute the dynamic fields in, so that a batch of base field
+    //updates can recompute each dynamic field at most once (rather than once per changed dependency)
+    let dynamic_field_names: Vec<&Ident> = dynamic_fields
+        .iter()
+        .map(|(field, _)| field.ident.as_ref().unwrap())
+        .collect();
+
+    let mut in_degree: HashMap<&Ident, usize> =
+        dynamic_field_names.iter().map(|name| (*name, 0)).collect();
+    let mut dependents: HashMap<&Ident, Vec<&Ident>> = HashMap::new();
+    //the dynamic dependencies of each dynamic field, used to walk a cycle back out for the error message
+    let mut requires: HashMap<&Ident, Vec<&Ident>> = HashMap::new();
+
+    dynamic_fields.iter().for_each(|(field, dynamic)| {
+        let field_name = field.ident.as_ref().unwrap();
+
+        let dynamic_dependencies: Vec<&Ident> = dynamic
+            .as_ref()
+            .unwrap()
+            .dependencies
+            .iter()
+            .filter(|dependency| in_degree.contains_key(dependency))
+            .collect();
+
+        dynamic_dependencies.into_iter().for_each(|dependency| {
+            *in_degree.get_mut(field_name).unwrap() += 1;
+            dependents.entry(dependency).or_default().push(field_name);
+            requires.entry(field_name).or_default().push(dependency);
+        });
+    });
+
+    //Kahn's algorithm: repeatedly emit dynamic fields with no outstanding dynamic dependencies
+    let mut ready: Vec<&Ident> = dynamic_field_names
+        .iter()
+        .filter(|name| in_degree[*name] == 0)
+        .copied()
+        .collect();
+    let mut topo_order: Vec<&Ident> = Vec::new();
+
+    while let Some(name) = ready.pop() {
+        topo_order.push(name);
+
+        if let Some(next) = dependents.get(name) {
+            next.iter().for_each(|dependent| {
+                let degree = in_degree.get_mut(dependent).unwrap();
+                *degree -= 1;
+
+                if *degree == 0 {
+                    ready.push(dependent);
+                }
+            });
+        }
+    }
+
+    if topo_order.len() != dynamic_field_names.len() {
+        //the fields left without a topological position are all part of a cycle (or depend on one);
+        //walk `requires` from one of them through other leftover fields until a field repeats, to
+        //report the actual path
+        let remaining: &Ident = dynamic_field_names
+            .iter()
+            .copied()
+            .find(|name| !topo_order.contains(name))
+            .unwrap();
+
+        let mut path: Vec<&Ident> = vec![remaining];
+        let cycle = loop {
+            let node = *path.last().unwrap();
+            let next: &Ident = requires
+                .get(node)
+                .and_then(|deps| deps.iter().copied().find(|dep| in_degree[*dep] > 0))
+                .unwrap();
+
+            if let Some(start) = path.iter().position(|visited| *visited == next) {
+                path.push(next);
+                break &path[start..];
+            }
+
+            path.push(next);
+        };
+
+        let path_description = cycle
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ");
+
+        let (_, offending_dynamic) = dynamic_fields
+            .iter()
+            .find(|(field, _)| field.ident.as_ref().unwrap() == cycle[0])
+            .unwrap();
+
+        return Err(syn::Error::new(
+            offending_dynamic.as_ref().unwrap().attr_span,
+            format!(
+                "cyclic dependency detected between dynamic fields of `{}`: {}",
+                ident, path_description
+            ),
+        ));
+    }
+
+    //a per-field dirty flag, set by the batch setters and consulted when recomputing dynamic fields
+    let dirty_ident = format_ident!("{}Dirty", ident);
+    let batch_ident = format_ident!("{}Batch", ident);
+
+    let dirty_field_decls = fields.iter().map(|field| {
+        let field_name = field.ident.as_ref().unwrap();
+        quote! { #field_name: bool }
+    });
+
+    //batch setters for non-dynamic fields: only flag the field as dirty, recomputation happens on commit
+    let batch_setter_methods = non_dynamic_fields.iter().map(|(field, _)| {
+        let field_name = field.ident.as_ref().unwrap();
+        let func_name = create_setter_ident(field_name);
+        let typ = &field.ty;
 
         quote! {
             #[inline]
-            pub fn #func_name(&mut self) {
-                self.#callable_name();
-                self.#updated_func_name();
+            pub fn #func_name(&mut self, value: #typ) {
+                self.target.#field_name = value;
+                self.dirty.#field_name = true;
+            }
+        }
+    });
+
+    //recompute each dynamic field (in topological order) if any of its dependencies changed
+    let commit_steps = topo_order.iter().map(|field_name| {
+        let (_, dynamic) = dynamic_fields
+            .iter()
+            .find(|(field, _)| field.ident.as_ref().unwrap() == *field_name)
+            .unwrap();
+        let dynamic = dynamic.as_ref().unwrap();
+        let callable_name = &dynamic.method_name;
+        let dependency_checks = dynamic
+            .dependencies
+            .iter()
+            .map(|dependency| quote! { self.dirty.#dependency });
+        let guard_check = dynamic
+            .guard
+            .as_ref()
+            .map(|guard| quote! { && self.target.#guard() });
+
+        quote! {
+            if (#(#dependency_checks)||*) #guard_check {
+                self.target.#callable_name();
+                self.dirty.#field_name = true;
             }
         }
     });
@@ -347,8 +803,58 @@ pub fn derive_dynamic(input: proc_macro::TokenStream) -> proc_macro::TokenStream
             #(
                 #update_methods
             )*
+
+            /// Recompute the dynamic fields in dependency order, once per transaction, based on
+            /// which base fields were changed via the batch setters inside `f`.
+            pub fn batch<F: FnOnce(&mut #batch_ident)>(&mut self, f: F) {
+                let mut batch = #batch_ident {
+                    target: self,
+                    dirty: #dirty_ident::default(),
+                };
+
+                f(&mut batch);
+                batch.commit();
+            }
+        }
+
+        #metadata_methods
+
+        #reflection_methods
+
+        #[derive(Default)]
+        struct #dirty_ident {
+            #(
+                #dirty_field_decls,
+            )*
+        }
+
+        /// A view onto a struct's fields while a batch of updates is in progress; setters here only
+        /// flag the field as changed, the dynamic fields are recomputed once the batch closure returns.
+        #vis struct #batch_ident<'a> {
+            target: &'a mut #ident,
+            dirty: #dirty_ident,
+        }
+
+        impl<'a> std::ops::Deref for #batch_ident<'a> {
+            type Target = #ident;
+
+            fn deref(&self) -> &Self::Target {
+                self.target
+            }
+        }
+
+        impl<'a> #batch_ident<'a> {
+            #(
+                #batch_setter_methods
+            )*
+
+            fn commit(&mut self) {
+                #(
+                    #commit_steps
+                )*
+            }
         }
     };
 
-    output.into()
+    Ok(output)
 }